@@ -0,0 +1,140 @@
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use nle_data::model::{Clip, TimeRange};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// One candidate input device and the format it would open with by default, so
+/// callers can let the user pick sample rate/channel count before arming the
+/// recorder.
+pub struct InputDeviceInfo {
+    pub name: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+pub fn enumerate_input_devices() -> Result<Vec<InputDeviceInfo>> {
+    let host = cpal::default_host();
+    let mut infos = Vec::new();
+    for device in host.input_devices()? {
+        let name = device.name().unwrap_or_else(|_| "Unknown Device".to_string());
+        let config = device.default_input_config()?;
+        infos.push(InputDeviceInfo {
+            name,
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+        });
+    }
+    Ok(infos)
+}
+
+struct RecordingState {
+    asset_id: Uuid,
+    start_timecode: u64,
+    sample_rate: u32,
+    channels: u16,
+    samples: Vec<f32>,
+}
+
+/// Records from a microphone/line-in device into memory, tagging the capture
+/// with a UUID asset id and the timeline timecode recording started at so it can
+/// become a `Clip` once the caller is done.
+pub struct InputCapture {
+    stream: cpal::Stream,
+    state: Arc<Mutex<RecordingState>>,
+}
+
+impl InputCapture {
+    /// Arms the recorder on `device` (or the host default if `None`). `start_timecode`
+    /// is the timeline position this clip will be placed at once recording stops.
+    /// `requested_config` overrides the sample rate/channel count a caller picked
+    /// from [`enumerate_input_devices`] instead of always opening the device's
+    /// default; the sample format still comes from the device, since cpal doesn't
+    /// expose per-format support independent of the default config.
+    pub fn start(
+        device: Option<&cpal::Device>,
+        start_timecode: u64,
+        requested_config: Option<cpal::StreamConfig>,
+    ) -> Result<Self> {
+        let host = cpal::default_host();
+        let device = match device {
+            Some(d) => d.clone(),
+            None => host
+                .default_input_device()
+                .ok_or_else(|| anyhow::anyhow!("No input device available"))?,
+        };
+        let default_config = device.default_input_config()?;
+        let sample_format = default_config.sample_format();
+        let stream_config = requested_config.unwrap_or_else(|| default_config.into());
+
+        let state = Arc::new(Mutex::new(RecordingState {
+            asset_id: Uuid::new_v4(),
+            start_timecode,
+            sample_rate: stream_config.sample_rate.0,
+            channels: stream_config.channels,
+            samples: Vec::new(),
+        }));
+
+        let stream = match sample_format {
+            cpal::SampleFormat::F32 => {
+                build_input_stream::<f32>(&device, &stream_config, Arc::clone(&state))?
+            }
+            cpal::SampleFormat::I16 => {
+                build_input_stream::<i16>(&device, &stream_config, Arc::clone(&state))?
+            }
+            cpal::SampleFormat::U16 => {
+                build_input_stream::<u16>(&device, &stream_config, Arc::clone(&state))?
+            }
+            _ => return Err(anyhow::anyhow!("Unsupported sample format")),
+        };
+        stream.play()?;
+
+        Ok(Self { stream, state })
+    }
+
+    /// Stops the stream and returns the recorded clip (ready for `Track::add`)
+    /// alongside its raw interleaved `f32` samples, so the caller can also cache
+    /// them with `AudioEngine::cache_clip_pcm`.
+    pub fn stop(self) -> (Clip, Vec<f32>) {
+        drop(self.stream);
+
+        let mut state = self.state.lock().unwrap();
+        let samples = std::mem::take(&mut state.samples);
+        let frames = samples.len() / state.channels.max(1) as usize;
+        let duration_us = frames as u64 * 1_000_000 / state.sample_rate as u64;
+
+        let clip = Clip::new(
+            format!("Recording {}", state.asset_id),
+            state.asset_id,
+            TimeRange::new(0, duration_us),
+            TimeRange::new(state.start_timecode, duration_us),
+            0,
+        );
+
+        (clip, samples)
+    }
+}
+
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    state: Arc<Mutex<RecordingState>>,
+) -> Result<cpal::Stream>
+where
+    T: cpal::Sample + cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let err_fn = |err| eprintln!("an error occurred on input stream: {}", err);
+
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mut state = state.lock().unwrap();
+            state.samples.extend(data.iter().map(|s| f32::from_sample(*s)));
+        },
+        err_fn,
+        None,
+    )?;
+
+    Ok(stream)
+}
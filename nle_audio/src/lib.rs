@@ -1,40 +1,101 @@
+mod capture;
+
+pub use capture::{enumerate_input_devices, InputCapture, InputDeviceInfo};
+
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use nle_data::model::Timeline;
+use ringbuf::{HeapConsumer, HeapProducer, HeapRb};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+// The playback FIFO; sized generously (~340ms at 48kHz stereo) so the producer
+// thread has slack against scheduling jitter without audible latency.
+const RING_BUFFER_FRAMES: usize = 16_384;
+// How many (resampled) frames the producer stages per iteration.
+const BLOCK_FRAMES: usize = 1024;
+// Sample rate decoded PCM is cached at (see `nle_media::audio_decoder::AudioDecoderActor`).
+const SOURCE_SAMPLE_RATE: u32 = 48_000;
+
+pub type PcmCache = Arc<Mutex<HashMap<[u8; 16], Vec<f32>>>>;
 
 pub struct AudioEngine {
     _stream: cpal::Stream,
+    pcm_cache: PcmCache,
 }
 
 impl AudioEngine {
-    pub fn new() -> Result<Self> {
+    /// `timeline` and `playhead` are shared with the video path so audio and the
+    /// `TimelineCompositor` stay in sync: whichever component drives the
+    /// transport forward updates `playhead`, and this engine just follows it.
+    pub fn new(timeline: Arc<Mutex<Timeline>>, playhead: Arc<AtomicU64>) -> Result<Self> {
         let host = cpal::default_host();
         let device = host
             .default_output_device()
             .ok_or_else(|| anyhow::anyhow!("No output device available"))?;
         let config = device.default_output_config()?;
+        let channels = config.channels() as usize;
+        let device_sample_rate = config.sample_rate().0;
+
+        let pcm_cache: PcmCache = Arc::new(Mutex::new(HashMap::new()));
+
+        let rb = HeapRb::<f32>::new(RING_BUFFER_FRAMES * channels);
+        let (producer, consumer) = rb.split();
+
+        spawn_producer(
+            timeline,
+            Arc::clone(&pcm_cache),
+            playhead,
+            producer,
+            device_sample_rate,
+            channels,
+        );
 
         let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => run::<f32>(&device, &config.into())?,
-            cpal::SampleFormat::I16 => run::<i16>(&device, &config.into())?,
-            cpal::SampleFormat::U16 => run::<u16>(&device, &config.into())?,
+            cpal::SampleFormat::F32 => run::<f32>(&device, &config.into(), consumer)?,
+            cpal::SampleFormat::I16 => run::<i16>(&device, &config.into(), consumer)?,
+            cpal::SampleFormat::U16 => run::<u16>(&device, &config.into(), consumer)?,
             _ => return Err(anyhow::anyhow!("Unsupported sample format")),
         };
 
-        Ok(Self { _stream: stream })
+        Ok(Self {
+            _stream: stream,
+            pcm_cache,
+        })
+    }
+
+    /// Registers fully-decoded PCM (at `SOURCE_SAMPLE_RATE`) for an asset so the
+    /// mixer can pull it by `asset_id` instead of decoding per frame.
+    pub fn cache_clip_pcm(&self, asset_id: Uuid, samples: Vec<f32>) {
+        self.pcm_cache
+            .lock()
+            .unwrap()
+            .insert(*asset_id.as_bytes(), samples);
     }
 }
 
-fn run<T>(device: &cpal::Device, config: &cpal::StreamConfig) -> Result<cpal::Stream>
+fn run<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut consumer: HeapConsumer<f32>,
+) -> Result<cpal::Stream>
 where
     T: cpal::Sample + cpal::FromSample<f32> + cpal::SizedSample,
 {
-    let channels = config.channels as usize;
     let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
 
     let stream = device.build_output_stream(
         config,
         move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-            write_silence(data, channels);
+            for sample in data.iter_mut() {
+                // An empty FIFO (producer stalled or fell behind) must never block
+                // the audio thread: emit silence for the missing samples instead.
+                let value = consumer.pop().unwrap_or(0.0);
+                *sample = T::from_sample(value);
+            }
         },
         err_fn,
         None,
@@ -44,27 +105,199 @@ where
     Ok(stream)
 }
 
-fn write_silence<T: cpal::Sample + cpal::FromSample<f32>>(data: &mut [T], _: usize) {
-    for sample in data.iter_mut() {
-        *sample = T::from_sample(0.0f32);
+fn spawn_producer(
+    timeline: Arc<Mutex<Timeline>>,
+    pcm_cache: PcmCache,
+    playhead: Arc<AtomicU64>,
+    mut producer: HeapProducer<f32>,
+    device_sample_rate: u32,
+    channels: usize,
+) {
+    std::thread::spawn(move || {
+        let mut resampler = LinearResampler::new(SOURCE_SAMPLE_RATE, device_sample_rate);
+
+        loop {
+            if producer.free_len() < BLOCK_FRAMES * channels {
+                std::thread::sleep(Duration::from_millis(2));
+                continue;
+            }
+
+            let time_us = playhead.load(Ordering::Acquire);
+            let source_frames = resampler.source_frames_for(BLOCK_FRAMES);
+            let mixed = {
+                let timeline = timeline.lock().unwrap();
+                let cache = pcm_cache.lock().unwrap();
+                mix_timecode(
+                    &timeline,
+                    &cache,
+                    time_us,
+                    channels,
+                    SOURCE_SAMPLE_RATE,
+                    source_frames,
+                )
+            };
+
+            for sample in resampler.process(&mixed, channels) {
+                if producer.push(sample).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Sums every clip audible at `time_us` across all audio tracks, applying each
+/// clip's gain, into an interleaved `f32` buffer of `frames` frames at
+/// `sample_rate`. Shared by the playback FIFO producer here and by
+/// `nle_export`'s export pipeline, which mixes at the export's configured
+/// sample rate rather than `SOURCE_SAMPLE_RATE`.
+pub fn mix_timecode(
+    timeline: &Timeline,
+    pcm_cache: &HashMap<[u8; 16], Vec<f32>>,
+    time_us: u64,
+    channels: usize,
+    sample_rate: u32,
+    frames: usize,
+) -> Vec<f32> {
+    let mut mixed = vec![0.0f32; frames * channels];
+
+    for track in &timeline.audio_tracks {
+        let Some(clip) = track.query(time_us) else {
+            continue;
+        };
+        let Some(pcm) = pcm_cache.get(&clip.asset_id) else {
+            continue;
+        };
+
+        let elapsed_us = time_us.saturating_sub(clip.timeline_range.start);
+        let source_start_us = clip.source_range.start + elapsed_us;
+        let start_frame = (source_start_us as u128 * sample_rate as u128 / 1_000_000) as usize;
+
+        for frame in 0..frames {
+            let sample_frame = start_frame + frame;
+            for ch in 0..channels {
+                if let Some(sample) = pcm.get(sample_frame * channels + ch) {
+                    mixed[frame * channels + ch] += sample * clip.gain;
+                }
+            }
+        }
     }
+
+    mixed
 }
 
-pub fn mix_signals(signals: &[&[f32]]) -> f32 {
-    signals.iter().map(|s| s.iter().sum::<f32>()).sum()
+/// A minimal linear-interpolation resampler. Good enough for scrub/preview
+/// playback where device and decode sample rates rarely match exactly. Export
+/// (`nle_export`) does not use it: it calls `mix_timecode` directly at its own
+/// configured sample rate.
+struct LinearResampler {
+    ratio: f64, // source frames per output frame
+}
+
+impl LinearResampler {
+    fn new(source_rate: u32, target_rate: u32) -> Self {
+        Self {
+            ratio: source_rate as f64 / target_rate as f64,
+        }
+    }
+
+    fn source_frames_for(&self, output_frames: usize) -> usize {
+        (output_frames as f64 * self.ratio).ceil() as usize + 1
+    }
+
+    fn process(&self, interleaved: &[f32], channels: usize) -> Vec<f32> {
+        let source_frames = interleaved.len() / channels.max(1);
+        if source_frames == 0 {
+            return Vec::new();
+        }
+
+        let output_frames = ((source_frames as f64 - 1.0) / self.ratio).floor().max(0.0) as usize;
+        let mut out = Vec::with_capacity(output_frames * channels);
+
+        for frame in 0..output_frames {
+            let pos = frame as f64 * self.ratio;
+            let lo = pos.floor() as usize;
+            let hi = (lo + 1).min(source_frames - 1);
+            let frac = (pos - lo as f64) as f32;
+
+            for ch in 0..channels {
+                let a = interleaved[lo * channels + ch];
+                let b = interleaved[hi * channels + ch];
+                out.push(a + (b - a) * frac);
+            }
+        }
+
+        out
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::mix_signals;
+    use super::*;
+    use nle_data::model::{Clip, TimeRange};
+
+    #[test]
+    fn test_mix_timecode_sums_overlapping_clips_with_gain() {
+        let mut timeline = Timeline::new();
+        timeline.add_audio_track();
+        timeline.add_audio_track();
+
+        let asset_a = Uuid::new_v4();
+        let asset_b = Uuid::new_v4();
+
+        let clip_a = Clip::new(
+            "A".to_string(),
+            asset_a,
+            TimeRange::new(0, 1_000_000),
+            TimeRange::new(0, 1_000_000),
+            0,
+        );
+        let clip_b = Clip::new(
+            "B".to_string(),
+            asset_b,
+            TimeRange::new(0, 1_000_000),
+            TimeRange::new(0, 1_000_000),
+            1,
+        )
+        .with_gain(0.5);
+
+        timeline.audio_tracks[0].add(clip_a);
+        timeline.audio_tracks[1].add(clip_b);
+
+        let mut cache = HashMap::new();
+        cache.insert(asset_a.into_bytes(), vec![1.0f32, 1.0, 1.0, 1.0]);
+        cache.insert(asset_b.into_bytes(), vec![2.0f32, 2.0, 2.0, 2.0]);
+
+        let mixed = mix_timecode(&timeline, &cache, 0, 2, 48_000, 2);
+
+        // clip_a at full gain (1.0) plus clip_b attenuated by its 0.5 gain.
+        assert_eq!(mixed, vec![2.0, 2.0, 2.0, 2.0]);
+    }
 
     #[test]
-    fn test_stereo_summing() {
-        let signal_a = vec![0.5, 0.5]; // Left, Right
-        let signal_b = vec![0.2, 0.2];
+    fn test_mix_timecode_skips_clips_with_no_cached_pcm() {
+        let mut timeline = Timeline::new();
+        timeline.add_audio_track();
+        let asset_id = Uuid::new_v4();
+        timeline.audio_tracks[0].add(Clip::new(
+            "Uncached".to_string(),
+            asset_id,
+            TimeRange::new(0, 1_000_000),
+            TimeRange::new(0, 1_000_000),
+            0,
+        ));
 
-        let mixed = mix_signals(&[&signal_a[..], &signal_b[..]]);
-        // 0.5 + 0.5 + 0.2 + 0.2 = 1.4
-        assert!((mixed - 1.4f32).abs() < f32::EPSILON);
+        let cache = HashMap::new();
+        let mixed = mix_timecode(&timeline, &cache, 0, 2, 48_000, 4);
+
+        assert_eq!(mixed, vec![0.0; 8]);
+    }
+
+    #[test]
+    fn test_linear_resampler_upsamples() {
+        let resampler = LinearResampler::new(1, 2); // ratio 0.5: 2x target rate
+        let out = resampler.process(&[0.0, 10.0, 20.0], 1);
+        assert_eq!(out, vec![0.0, 5.0, 10.0, 15.0]);
     }
 }
+
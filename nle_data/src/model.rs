@@ -32,7 +32,7 @@ impl TimeRange {
 }
 
 #[derive(
-    Debug, Clone, PartialEq, Eq, Archive, Deserialize, Serialize, SerdeSerialize, SerdeDeserialize,
+    Debug, Clone, PartialEq, Archive, Deserialize, Serialize, SerdeSerialize, SerdeDeserialize,
 )]
 #[archive(check_bytes)]
 pub struct Clip {
@@ -41,6 +41,8 @@ pub struct Clip {
     pub timeline_range: TimeRange, // Position in timeline
     pub track_index: u32,
     pub name: String,
+    pub gain: f32,    // Linear amplitude multiplier applied when mixing audio clips
+    pub opacity: f32, // Alpha multiplier applied when compositing video clips
 }
 
 impl Clip {
@@ -57,12 +59,24 @@ impl Clip {
             timeline_range,
             track_index,
             name,
+            gain: 1.0,
+            opacity: 1.0,
         }
     }
 
     pub fn asset_uuid(&self) -> Uuid {
         Uuid::from_bytes(self.asset_id)
     }
+
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -0,0 +1,688 @@
+//! Renders a `Timeline` to a muxed output file: step the playhead at a fixed
+//! frame rate, ask `TimelineCompositor` for each composited frame, and feed the
+//! result into an FFmpeg encoder + muxer chosen from the output path's extension.
+
+use anyhow::Result;
+use ffmpeg_sys_next as ffi;
+use nle_audio::PcmCache;
+use nle_data::model::Timeline;
+use nle_render::{wgpu, RenderEngine, TimelineCompositor};
+use std::ffi::CString;
+use std::ptr;
+
+/// Mirrors the knobs a real export dialog exposes: codec choice, bitrate, GOP
+/// structure, and the audio side of the same.
+pub struct EncoderConfig {
+    pub video_codec_name: String,
+    pub video_bitrate: i64,
+    pub pixel_format: ffi::AVPixelFormat,
+    pub gop_size: i32,
+    pub audio_codec_name: String,
+    pub audio_bitrate: i64,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: u32,
+    pub audio_sample_rate: u32,
+    pub audio_channels: u32,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self {
+            video_codec_name: "libx264".to_string(),
+            video_bitrate: 8_000_000,
+            pixel_format: ffi::AVPixelFormat::AV_PIX_FMT_YUV420P,
+            gop_size: 12,
+            audio_codec_name: "aac".to_string(),
+            audio_bitrate: 192_000,
+            width: 1920,
+            height: 1080,
+            frame_rate: 30,
+            audio_sample_rate: 48_000,
+            audio_channels: 2,
+        }
+    }
+}
+
+struct VideoEncoder {
+    codec_ctx: *mut ffi::AVCodecContext,
+    stream_index: i32,
+    frame_count: i64,
+}
+
+struct AudioEncoder {
+    codec_ctx: *mut ffi::AVCodecContext,
+    stream_index: i32,
+    fifo: *mut ffi::AVAudioFifo,
+    samples_written: i64,
+}
+
+/// Owns the muxer plus the video/audio encoder contexts for one export run.
+pub struct ExportSession {
+    fmt_ctx: *mut ffi::AVFormatContext,
+    video: VideoEncoder,
+    audio: Option<AudioEncoder>,
+    config: EncoderConfig,
+}
+
+unsafe impl Send for ExportSession {}
+
+impl ExportSession {
+    pub fn create(output_path: &str, config: EncoderConfig, with_audio: bool) -> Result<Self> {
+        unsafe {
+            let c_path = CString::new(output_path)?;
+            let mut fmt_ctx: *mut ffi::AVFormatContext = ptr::null_mut();
+            if ffi::avformat_alloc_output_context2(
+                &mut fmt_ctx,
+                ptr::null(),
+                ptr::null(),
+                c_path.as_ptr(),
+            ) < 0
+                || fmt_ctx.is_null()
+            {
+                return Err(anyhow::anyhow!(
+                    "could not deduce output format from {}",
+                    output_path
+                ));
+            }
+
+            let video = Self::open_video_encoder(fmt_ctx, &config)?;
+            let audio = if with_audio {
+                Some(Self::open_audio_encoder(fmt_ctx, &config)?)
+            } else {
+                None
+            };
+
+            if (*(*fmt_ctx).oformat).flags & ffi::AVFMT_NOFILE as i32 == 0 {
+                if ffi::avio_open(&mut (*fmt_ctx).pb, c_path.as_ptr(), ffi::AVIO_FLAG_WRITE) < 0 {
+                    return Err(anyhow::anyhow!("avio_open failed for {}", output_path));
+                }
+            }
+
+            if ffi::avformat_write_header(fmt_ctx, ptr::null_mut()) < 0 {
+                return Err(anyhow::anyhow!("avformat_write_header failed"));
+            }
+
+            Ok(Self {
+                fmt_ctx,
+                video,
+                audio,
+                config,
+            })
+        }
+    }
+
+    unsafe fn open_video_encoder(
+        fmt_ctx: *mut ffi::AVFormatContext,
+        config: &EncoderConfig,
+    ) -> Result<VideoEncoder> {
+        let c_name = CString::new(config.video_codec_name.clone())?;
+        let codec = ffi::avcodec_find_encoder_by_name(c_name.as_ptr());
+        if codec.is_null() {
+            return Err(anyhow::anyhow!(
+                "unknown video encoder {}",
+                config.video_codec_name
+            ));
+        }
+
+        let codec_ctx = ffi::avcodec_alloc_context3(codec);
+        if codec_ctx.is_null() {
+            return Err(anyhow::anyhow!("avcodec_alloc_context3 (video) failed"));
+        }
+        (*codec_ctx).width = config.width as i32;
+        (*codec_ctx).height = config.height as i32;
+        (*codec_ctx).time_base = ffi::AVRational {
+            num: 1,
+            den: config.frame_rate as i32,
+        };
+        (*codec_ctx).framerate = ffi::AVRational {
+            num: config.frame_rate as i32,
+            den: 1,
+        };
+        (*codec_ctx).gop_size = config.gop_size;
+        (*codec_ctx).pix_fmt = config.pixel_format;
+        (*codec_ctx).bit_rate = config.video_bitrate;
+        if (*(*fmt_ctx).oformat).flags & ffi::AVFMT_GLOBALHEADER as i32 != 0 {
+            (*codec_ctx).flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+        }
+
+        if ffi::avcodec_open2(codec_ctx, codec, ptr::null_mut()) < 0 {
+            return Err(anyhow::anyhow!("avcodec_open2 (video) failed"));
+        }
+
+        let stream = ffi::avformat_new_stream(fmt_ctx, ptr::null());
+        if stream.is_null() {
+            return Err(anyhow::anyhow!("avformat_new_stream (video) failed"));
+        }
+        if ffi::avcodec_parameters_from_context((*stream).codecpar, codec_ctx) < 0 {
+            return Err(anyhow::anyhow!(
+                "avcodec_parameters_from_context (video) failed"
+            ));
+        }
+        (*stream).time_base = (*codec_ctx).time_base;
+
+        Ok(VideoEncoder {
+            codec_ctx,
+            stream_index: (*stream).index,
+            frame_count: 0,
+        })
+    }
+
+    unsafe fn open_audio_encoder(
+        fmt_ctx: *mut ffi::AVFormatContext,
+        config: &EncoderConfig,
+    ) -> Result<AudioEncoder> {
+        let c_name = CString::new(config.audio_codec_name.clone())?;
+        let codec = ffi::avcodec_find_encoder_by_name(c_name.as_ptr());
+        if codec.is_null() {
+            return Err(anyhow::anyhow!(
+                "unknown audio encoder {}",
+                config.audio_codec_name
+            ));
+        }
+
+        let codec_ctx = ffi::avcodec_alloc_context3(codec);
+        if codec_ctx.is_null() {
+            return Err(anyhow::anyhow!("avcodec_alloc_context3 (audio) failed"));
+        }
+        (*codec_ctx).sample_rate = config.audio_sample_rate as i32;
+        (*codec_ctx).sample_fmt = ffi::AVSampleFormat::AV_SAMPLE_FMT_FLTP;
+        ffi::av_channel_layout_default(&mut (*codec_ctx).ch_layout, config.audio_channels as i32);
+        (*codec_ctx).bit_rate = config.audio_bitrate;
+        (*codec_ctx).time_base = ffi::AVRational {
+            num: 1,
+            den: config.audio_sample_rate as i32,
+        };
+        if (*(*fmt_ctx).oformat).flags & ffi::AVFMT_GLOBALHEADER as i32 != 0 {
+            (*codec_ctx).flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+        }
+
+        if ffi::avcodec_open2(codec_ctx, codec, ptr::null_mut()) < 0 {
+            return Err(anyhow::anyhow!("avcodec_open2 (audio) failed"));
+        }
+
+        let stream = ffi::avformat_new_stream(fmt_ctx, ptr::null());
+        if stream.is_null() {
+            return Err(anyhow::anyhow!("avformat_new_stream (audio) failed"));
+        }
+        if ffi::avcodec_parameters_from_context((*stream).codecpar, codec_ctx) < 0 {
+            return Err(anyhow::anyhow!(
+                "avcodec_parameters_from_context (audio) failed"
+            ));
+        }
+        (*stream).time_base = (*codec_ctx).time_base;
+
+        // The encoder usually requires a fixed frame size (e.g. 1024 samples for
+        // AAC) but `feed_audio` mixes one video frame's worth of samples per
+        // block; the AVAudioFifo repackages one into the other.
+        let fifo = ffi::av_audio_fifo_alloc(
+            (*codec_ctx).sample_fmt,
+            config.audio_channels as i32,
+            1,
+        );
+        if fifo.is_null() {
+            return Err(anyhow::anyhow!("av_audio_fifo_alloc failed"));
+        }
+
+        Ok(AudioEncoder {
+            codec_ctx,
+            stream_index: (*stream).index,
+            fifo,
+            samples_written: 0,
+        })
+    }
+
+    /// Steps the playhead across `timeline` at `config.frame_rate`, asking
+    /// `compositor` to composite and upload each frame, and pushes the result
+    /// through the video encoder. `pcm_cache` feeds the audio path the same way
+    /// `nle_audio`'s mixer does.
+    pub async fn run(
+        &mut self,
+        timeline: &Timeline,
+        render: &RenderEngine,
+        compositor: &mut TimelineCompositor,
+        pcm_cache: Option<&PcmCache>,
+        duration_us: u64,
+    ) -> Result<()> {
+        let frame_duration_us = 1_000_000 / self.config.frame_rate as u64;
+        let mut time_us = 0u64;
+
+        let output_texture = render.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("nle_export Offscreen Target"),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        while time_us < duration_us {
+            compositor
+                .prepare(render.device(), render.queue(), time_us)
+                .await?;
+
+            let mut encoder = render
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("nle_export Frame Encoder"),
+                });
+            nle_render::RenderNode::encode(compositor, &mut encoder, &output_view);
+            render.queue().submit(Some(encoder.finish()));
+
+            let rgba = read_texture_rgba(
+                render.device(),
+                render.queue(),
+                &output_texture,
+                self.config.width,
+                self.config.height,
+            );
+            self.encode_video_frame(&rgba, time_us)?;
+
+            if let Some(cache) = pcm_cache {
+                self.feed_audio(timeline, cache, time_us, frame_duration_us)?;
+            }
+
+            time_us += frame_duration_us;
+        }
+
+        self.flush_audio()?;
+        self.flush_encoder(self.video.codec_ctx, self.video.stream_index)?;
+        if let Some(audio) = &self.audio {
+            self.flush_encoder(audio.codec_ctx, audio.stream_index)?;
+        }
+
+        unsafe {
+            ffi::av_write_trailer(self.fmt_ctx);
+        }
+        Ok(())
+    }
+
+    fn encode_video_frame(&mut self, rgba: &[u8], timecode_us: u64) -> Result<()> {
+        unsafe {
+            let frame = ffi::av_frame_alloc();
+            (*frame).format = self.config.pixel_format as i32;
+            (*frame).width = self.config.width as i32;
+            (*frame).height = self.config.height as i32;
+            if ffi::av_frame_get_buffer(frame, 0) < 0 {
+                ffi::av_frame_free(&mut (frame as *mut _));
+                return Err(anyhow::anyhow!("av_frame_get_buffer failed"));
+            }
+
+            // Composited output is RGBA8; convert into the encoder's pixel format
+            // (commonly YUV420P) with sws_scale rather than hand-rolling BT.601.
+            let sws_ctx = ffi::sws_getContext(
+                self.config.width as i32,
+                self.config.height as i32,
+                ffi::AVPixelFormat::AV_PIX_FMT_RGBA,
+                self.config.width as i32,
+                self.config.height as i32,
+                self.config.pixel_format,
+                ffi::SWS_BILINEAR,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null(),
+            );
+            if sws_ctx.is_null() {
+                ffi::av_frame_free(&mut (frame as *mut _));
+                return Err(anyhow::anyhow!("sws_getContext failed"));
+            }
+
+            let src_stride = [4 * self.config.width as i32, 0, 0, 0];
+            let src_slices = [rgba.as_ptr(), ptr::null(), ptr::null(), ptr::null()];
+            ffi::sws_scale(
+                sws_ctx,
+                src_slices.as_ptr(),
+                src_stride.as_ptr(),
+                0,
+                self.config.height as i32,
+                (*frame).data.as_ptr() as *const *mut u8,
+                (*frame).linesize.as_ptr(),
+            );
+            ffi::sws_freeContext(sws_ctx);
+
+            (*frame).pts = ffi::av_rescale_q(
+                timecode_us as i64,
+                ffi::AVRational { num: 1, den: 1_000_000 },
+                (*self.video.codec_ctx).time_base,
+            );
+            self.video.frame_count += 1;
+
+            let ret = self.send_and_mux(self.video.codec_ctx, frame, self.video.stream_index);
+            ffi::av_frame_free(&mut (frame as *mut _));
+            ret
+        }
+    }
+
+    fn feed_audio(
+        &mut self,
+        timeline: &Timeline,
+        pcm_cache: &PcmCache,
+        time_us: u64,
+        block_duration_us: u64,
+    ) -> Result<()> {
+        let Some(audio) = &mut self.audio else {
+            return Ok(());
+        };
+        let channels = self.config.audio_channels as usize;
+        let sample_rate = self.config.audio_sample_rate;
+        let block_frames =
+            (block_duration_us as u128 * sample_rate as u128 / 1_000_000) as usize;
+        let block_end_us = time_us + block_duration_us;
+
+        // Unlike nle_audio's preview-quality mixer (which picks one clip per block
+        // for a cheap scrub/playback path), export re-mixes around every clip
+        // boundary that falls inside this block so a cut doesn't bleed up to a
+        // block's worth (~1 video frame) of the outgoing clip past its out-point.
+        let mut mixed = Vec::with_capacity(block_frames * channels);
+        {
+            let cache = pcm_cache.lock().unwrap();
+            let mut cur_us = time_us;
+            let mut frames_left = block_frames;
+            while frames_left > 0 {
+                let boundary_us =
+                    next_clip_boundary_us(timeline, cur_us, block_end_us).unwrap_or(block_end_us);
+                let seg_frames = (((boundary_us - cur_us) as u128 * sample_rate as u128
+                    / 1_000_000) as usize)
+                    .clamp(1, frames_left);
+
+                mixed.extend_from_slice(&nle_audio::mix_timecode(
+                    timeline,
+                    &cache,
+                    cur_us,
+                    channels,
+                    sample_rate,
+                    seg_frames,
+                ));
+
+                cur_us += seg_frames as u64 * 1_000_000 / sample_rate as u64;
+                frames_left -= seg_frames;
+            }
+        }
+
+        unsafe {
+            let planar = interleaved_to_planar(&mixed, channels);
+            let plane_ptrs: Vec<*const u8> = planar.iter().map(|p| p.as_ptr() as *const u8).collect();
+            if ffi::av_audio_fifo_write(
+                audio.fifo,
+                plane_ptrs.as_ptr() as *mut *mut std::ffi::c_void,
+                block_frames as i32,
+            ) < block_frames as i32
+            {
+                return Err(anyhow::anyhow!("av_audio_fifo_write short write"));
+            }
+
+            let frame_size = (*audio.codec_ctx).frame_size;
+            while ffi::av_audio_fifo_size(audio.fifo) >= frame_size {
+                let frame = ffi::av_frame_alloc();
+                (*frame).nb_samples = frame_size;
+                (*frame).format = (*audio.codec_ctx).sample_fmt as i32;
+                (*frame).ch_layout = (*audio.codec_ctx).ch_layout.clone();
+                (*frame).sample_rate = (*audio.codec_ctx).sample_rate;
+                if ffi::av_frame_get_buffer(frame, 0) < 0 {
+                    ffi::av_frame_free(&mut (frame as *mut _));
+                    return Err(anyhow::anyhow!("av_frame_get_buffer (audio) failed"));
+                }
+                if ffi::av_audio_fifo_read(
+                    audio.fifo,
+                    (*frame).data.as_mut_ptr() as *mut *mut std::ffi::c_void,
+                    frame_size,
+                ) < frame_size
+                {
+                    ffi::av_frame_free(&mut (frame as *mut _));
+                    return Err(anyhow::anyhow!("av_audio_fifo_read short read"));
+                }
+
+                (*frame).pts = audio.samples_written;
+                audio.samples_written += frame_size as i64;
+
+                let ret = self.send_and_mux(audio.codec_ctx, frame, audio.stream_index);
+                ffi::av_frame_free(&mut (frame as *mut _));
+                ret?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pads whatever's left in the FIFO (fewer than one encoder frame) with
+    /// silence and pushes it through, so the tail of the audio track isn't
+    /// silently dropped relative to video.
+    fn flush_audio(&mut self) -> Result<()> {
+        let Some(audio) = &mut self.audio else {
+            return Ok(());
+        };
+
+        unsafe {
+            let remaining = ffi::av_audio_fifo_size(audio.fifo);
+            if remaining <= 0 {
+                return Ok(());
+            }
+
+            let frame_size = (*audio.codec_ctx).frame_size;
+
+            let frame = ffi::av_frame_alloc();
+            (*frame).nb_samples = frame_size;
+            (*frame).format = (*audio.codec_ctx).sample_fmt as i32;
+            (*frame).ch_layout = (*audio.codec_ctx).ch_layout.clone();
+            (*frame).sample_rate = (*audio.codec_ctx).sample_rate;
+            if ffi::av_frame_get_buffer(frame, 0) < 0 {
+                ffi::av_frame_free(&mut (frame as *mut _));
+                return Err(anyhow::anyhow!("av_frame_get_buffer (audio flush) failed"));
+            }
+
+            if ffi::av_audio_fifo_read(
+                audio.fifo,
+                (*frame).data.as_mut_ptr() as *mut *mut std::ffi::c_void,
+                remaining,
+            ) < remaining
+            {
+                ffi::av_frame_free(&mut (frame as *mut _));
+                return Err(anyhow::anyhow!("av_audio_fifo_read (flush) failed"));
+            }
+
+            // Pad the rest of the final frame with silence so the encoder still
+            // gets a full frame_size block.
+            if ffi::av_samples_set_silence(
+                (*frame).data.as_mut_ptr(),
+                remaining,
+                frame_size - remaining,
+                (*audio.codec_ctx).ch_layout.nb_channels,
+                (*audio.codec_ctx).sample_fmt,
+            ) < 0
+            {
+                ffi::av_frame_free(&mut (frame as *mut _));
+                return Err(anyhow::anyhow!("av_samples_set_silence failed"));
+            }
+
+            (*frame).pts = audio.samples_written;
+            audio.samples_written += frame_size as i64;
+
+            let ret = self.send_and_mux(audio.codec_ctx, frame, audio.stream_index);
+            ffi::av_frame_free(&mut (frame as *mut _));
+            ret
+        }
+    }
+
+    /// Drains `avcodec_send_frame(ctx, None)` to flush any packets the encoder is
+    /// still holding once input stops.
+    fn flush_encoder(&self, codec_ctx: *mut ffi::AVCodecContext, stream_index: i32) -> Result<()> {
+        unsafe {
+            if ffi::avcodec_send_frame(codec_ctx, ptr::null()) < 0 {
+                return Ok(());
+            }
+            loop {
+                let packet = ffi::av_packet_alloc();
+                let ret = ffi::avcodec_receive_packet(codec_ctx, packet);
+                if ret < 0 {
+                    ffi::av_packet_free(&mut (packet as *mut _));
+                    break;
+                }
+                self.mux_packet(packet, codec_ctx, stream_index);
+            }
+        }
+        Ok(())
+    }
+
+    unsafe fn send_and_mux(
+        &self,
+        codec_ctx: *mut ffi::AVCodecContext,
+        frame: *mut ffi::AVFrame,
+        stream_index: i32,
+    ) -> Result<()> {
+        const AVERROR_EAGAIN: i32 = -(ffi::EAGAIN as i32);
+
+        if ffi::avcodec_send_frame(codec_ctx, frame) < 0 {
+            return Err(anyhow::anyhow!("avcodec_send_frame failed"));
+        }
+
+        loop {
+            let packet = ffi::av_packet_alloc();
+            let ret = ffi::avcodec_receive_packet(codec_ctx, packet);
+            if ret == AVERROR_EAGAIN || ret == ffi::AVERROR_EOF {
+                ffi::av_packet_free(&mut (packet as *mut _));
+                break;
+            } else if ret < 0 {
+                ffi::av_packet_free(&mut (packet as *mut _));
+                return Err(anyhow::anyhow!("avcodec_receive_packet failed"));
+            }
+            self.mux_packet(packet, codec_ctx, stream_index);
+        }
+        Ok(())
+    }
+
+    /// `av_interleaved_write_frame` reorders by DTS across streams internally, so
+    /// video and audio packets can simply be handed over as they're produced.
+    unsafe fn mux_packet(
+        &self,
+        packet: *mut ffi::AVPacket,
+        codec_ctx: *mut ffi::AVCodecContext,
+        stream_index: i32,
+    ) {
+        let stream = *(*self.fmt_ctx).streams.offset(stream_index as isize);
+        ffi::av_packet_rescale_ts(packet, (*codec_ctx).time_base, (*stream).time_base);
+        (*packet).stream_index = stream_index;
+        ffi::av_interleaved_write_frame(self.fmt_ctx, packet);
+        ffi::av_packet_free(&mut (packet as *mut _));
+    }
+}
+
+impl Drop for ExportSession {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::avcodec_free_context(&mut self.video.codec_ctx);
+            if let Some(audio) = &mut self.audio {
+                ffi::av_audio_fifo_free(audio.fifo);
+                ffi::avcodec_free_context(&mut audio.codec_ctx);
+            }
+            if !self.fmt_ctx.is_null()
+                && (*(*self.fmt_ctx).oformat).flags & ffi::AVFMT_NOFILE as i32 == 0
+            {
+                ffi::avio_closep(&mut (*self.fmt_ctx).pb);
+            }
+            ffi::avformat_free_context(self.fmt_ctx);
+        }
+    }
+}
+
+fn read_texture_rgba(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    width: u32,
+    height: u32,
+) -> Vec<u8> {
+    let bytes_per_row = 4 * width;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("nle_export Readback Buffer"),
+        size: (bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("nle_export Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (tx, rx) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = tx.send(result);
+    });
+
+    // `run` drives this once per exported frame from an async fn on the shared
+    // tokio runtime alongside DecoderActor/AudioDecoderActor's message loops;
+    // block_in_place tells tokio this thread is about to block synchronously so
+    // it can move other ready tasks onto a different worker instead of starving
+    // them for the GPU readback's duration.
+    tokio::task::block_in_place(|| {
+        device.poll(wgpu::Maintain::Wait);
+        rx.recv().unwrap().unwrap();
+    });
+
+    slice.get_mapped_range().to_vec()
+}
+
+/// Earliest clip start/end on any audio track strictly inside `(start_us, end_us)`,
+/// i.e. the next point within this block where the set of audible clips changes.
+fn next_clip_boundary_us(timeline: &Timeline, start_us: u64, end_us: u64) -> Option<u64> {
+    let mut next = None;
+    for track in &timeline.audio_tracks {
+        for (range, _clip) in track.get_clips().iter() {
+            for edge in [range.start, range.end] {
+                if edge > start_us && edge < end_us {
+                    next = Some(next.map_or(edge, |n: u64| n.min(edge)));
+                }
+            }
+        }
+    }
+    next
+}
+
+/// The FLTP sample format AAC/most audio encoders expect is planar: one
+/// contiguous buffer per channel rather than interleaved samples.
+fn interleaved_to_planar(interleaved: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    let frames = interleaved.len() / channels.max(1);
+    let mut planes = vec![Vec::with_capacity(frames); channels];
+    for frame in 0..frames {
+        for (ch, plane) in planes.iter_mut().enumerate() {
+            plane.push(interleaved[frame * channels + ch]);
+        }
+    }
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interleaved_to_planar_splits_channels() {
+        let interleaved = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let planes = interleaved_to_planar(&interleaved, 2);
+
+        assert_eq!(planes, vec![vec![1.0, 3.0, 5.0], vec![2.0, 4.0, 6.0]]);
+    }
+}
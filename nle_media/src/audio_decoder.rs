@@ -0,0 +1,277 @@
+use crate::frame::open_custom_io_format_context;
+use ffmpeg_sys_next as ffi;
+use std::io::Cursor;
+use std::ptr;
+use tokio::sync::{mpsc, oneshot};
+
+const AVERROR_EAGAIN: i32 = -(ffi::EAGAIN as i32);
+
+/// Distinguishes the ways decoding an audio asset can fail, so a caller (e.g. the
+/// import UI) can tell "this file isn't an audio format we support" apart from a
+/// transient I/O hiccup or a genuine bitstream problem.
+#[derive(Debug)]
+pub enum AudioDecodeError {
+    UnsupportedFormat(String),
+    Io(String),
+    Decode(String),
+}
+
+impl std::fmt::Display for AudioDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedFormat(msg) => write!(f, "unsupported audio format: {msg}"),
+            Self::Io(msg) => write!(f, "audio I/O error: {msg}"),
+            Self::Decode(msg) => write!(f, "audio decode error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for AudioDecodeError {}
+
+pub enum AudioDecoderMessage {
+    DecodeAudioData {
+        data: Vec<u8>,
+        target_sample_rate: u32,
+        response: oneshot::Sender<Result<Vec<f32>, AudioDecodeError>>,
+    },
+}
+
+/// Mirrors `DecoderActor`'s shape but for a full off-thread decode-to-buffer
+/// instead of per-frame video serving: the UI decodes an asset once on import and
+/// caches the PCM (see `nle_audio::AudioEngine::cache_clip_pcm`) instead of
+/// re-decoding on every mixer tick.
+pub struct AudioDecoderActor {
+    receiver: mpsc::Receiver<AudioDecoderMessage>,
+}
+
+impl AudioDecoderActor {
+    pub fn new(receiver: mpsc::Receiver<AudioDecoderMessage>) -> Self {
+        Self { receiver }
+    }
+
+    pub async fn run(mut self) {
+        while let Some(msg) = self.receiver.recv().await {
+            match msg {
+                AudioDecoderMessage::DecodeAudioData {
+                    data,
+                    target_sample_rate,
+                    response,
+                } => {
+                    // A full-asset decode is CPU-bound FFmpeg work that can take
+                    // a while on a large file; run it on the blocking pool so it
+                    // doesn't stall the async runtime.
+                    let result = tokio::task::spawn_blocking(move || {
+                        decode_to_pcm(&data, target_sample_rate)
+                    })
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(AudioDecodeError::Decode("decode task panicked".to_string()))
+                    });
+                    let _ = response.send(result);
+                }
+            }
+        }
+    }
+}
+
+/// Helper to spawn an `AudioDecoderActor`, mirroring `spawn_decoder`.
+pub fn spawn_audio_decoder() -> mpsc::Sender<AudioDecoderMessage> {
+    let (tx, rx) = mpsc::channel(8);
+    let actor = AudioDecoderActor::new(rx);
+
+    tokio::spawn(async move {
+        actor.run().await;
+    });
+
+    tx
+}
+
+/// Decodes the whole of `data` (a compressed audio asset already read into
+/// memory) into an interleaved `f32` PCM buffer resampled to `target_sample_rate`.
+/// Builds the `AVFormatContext` through `nle_media::frame`'s shared custom-AVIO
+/// helper (the same one `FfmpegSource::from_reader` uses) rather than
+/// re-deriving the memory-reader trampoline here.
+fn decode_to_pcm(data: &[u8], target_sample_rate: u32) -> Result<Vec<f32>, AudioDecodeError> {
+    unsafe {
+        let (fmt_ctx, avio) = open_custom_io_format_context(Cursor::new(data.to_vec()))
+            .map_err(|e| AudioDecodeError::Io(e.to_string()))?;
+
+        // `decode_with_format_context` closes `fmt_ctx` itself on every path (it
+        // never frees the custom AVIOContext, since AVFMT_FLAG_CUSTOM_IO tells
+        // avformat_close_input to leave `pb` alone) — only the AVIO side is ours
+        // to free here.
+        let result = decode_with_format_context(fmt_ctx, target_sample_rate);
+        drop(avio);
+
+        result
+    }
+}
+
+unsafe fn decode_with_format_context(
+    mut fmt_ctx: *mut ffi::AVFormatContext,
+    target_sample_rate: u32,
+) -> Result<Vec<f32>, AudioDecodeError> {
+    if ffi::avformat_find_stream_info(fmt_ctx, ptr::null_mut()) < 0 {
+        ffi::avformat_close_input(&mut fmt_ctx);
+        return Err(AudioDecodeError::Decode("avformat_find_stream_info failed".to_string()));
+    }
+
+    let stream_index = ffi::av_find_best_stream(
+        fmt_ctx,
+        ffi::AVMediaType::AVMEDIA_TYPE_AUDIO,
+        -1,
+        -1,
+        ptr::null_mut(),
+        0,
+    );
+    if stream_index < 0 {
+        ffi::avformat_close_input(&mut fmt_ctx);
+        return Err(AudioDecodeError::UnsupportedFormat("no audio stream found".to_string()));
+    }
+
+    let stream = *(*fmt_ctx).streams.offset(stream_index as isize);
+    let codec_par = (*stream).codecpar;
+    let decoder = ffi::avcodec_find_decoder((*codec_par).codec_id);
+    if decoder.is_null() {
+        ffi::avformat_close_input(&mut fmt_ctx);
+        return Err(AudioDecodeError::UnsupportedFormat(format!(
+            "no decoder for codec id {:?}",
+            (*codec_par).codec_id
+        )));
+    }
+
+    let codec_ctx = ffi::avcodec_alloc_context3(decoder);
+    if codec_ctx.is_null()
+        || ffi::avcodec_parameters_to_context(codec_ctx, codec_par) < 0
+        || ffi::avcodec_open2(codec_ctx, decoder, ptr::null_mut()) < 0
+    {
+        let mut ctx = codec_ctx;
+        if !ctx.is_null() {
+            ffi::avcodec_free_context(&mut ctx);
+        }
+        ffi::avformat_close_input(&mut fmt_ctx);
+        return Err(AudioDecodeError::Decode("failed to open audio decoder".to_string()));
+    }
+
+    let mut out_ch_layout = ffi::AVChannelLayout::default();
+    ffi::av_channel_layout_default(&mut out_ch_layout, (*codec_ctx).ch_layout.nb_channels);
+    let channels = out_ch_layout.nb_channels as usize;
+
+    let mut swr_ctx: *mut ffi::SwrContext = ptr::null_mut();
+    let ret = ffi::swr_alloc_set_opts2(
+        &mut swr_ctx,
+        &out_ch_layout,
+        ffi::AVSampleFormat::AV_SAMPLE_FMT_FLT,
+        target_sample_rate as i32,
+        &(*codec_ctx).ch_layout,
+        (*codec_ctx).sample_fmt,
+        (*codec_ctx).sample_rate,
+        0,
+        ptr::null_mut(),
+    );
+    if ret < 0 || swr_ctx.is_null() || ffi::swr_init(swr_ctx) < 0 {
+        ffi::avcodec_free_context(&mut (codec_ctx as *mut _));
+        ffi::avformat_close_input(&mut fmt_ctx);
+        return Err(AudioDecodeError::Decode("failed to initialize resampler".to_string()));
+    }
+
+    let mut pcm: Vec<f32> = Vec::new();
+    let packet = ffi::av_packet_alloc();
+    let frame = ffi::av_frame_alloc();
+
+    let decode_result = (|| -> Result<(), AudioDecodeError> {
+        loop {
+            let read_ret = ffi::av_read_frame(fmt_ctx, packet);
+            if read_ret == ffi::AVERROR_EOF {
+                ffi::avcodec_send_packet(codec_ctx, ptr::null());
+            } else if read_ret < 0 {
+                return Err(AudioDecodeError::Io("av_read_frame failed".to_string()));
+            } else if (*packet).stream_index != stream_index {
+                ffi::av_packet_unref(packet);
+                continue;
+            } else {
+                let send_ret = ffi::avcodec_send_packet(codec_ctx, packet);
+                ffi::av_packet_unref(packet);
+                if send_ret < 0 && send_ret != AVERROR_EAGAIN {
+                    return Err(AudioDecodeError::Decode("avcodec_send_packet failed".to_string()));
+                }
+            }
+
+            loop {
+                let recv_ret = ffi::avcodec_receive_frame(codec_ctx, frame);
+                if recv_ret == AVERROR_EAGAIN {
+                    break;
+                } else if recv_ret == ffi::AVERROR_EOF {
+                    return Ok(());
+                } else if recv_ret < 0 {
+                    return Err(AudioDecodeError::Decode("avcodec_receive_frame failed".to_string()));
+                }
+
+                resample_frame_into(swr_ctx, frame, channels, &mut pcm)?;
+            }
+
+            if read_ret == ffi::AVERROR_EOF {
+                return Ok(());
+            }
+        }
+    })();
+
+    ffi::av_frame_free(&mut (frame as *mut _));
+    ffi::av_packet_free(&mut (packet as *mut _));
+    ffi::swr_free(&mut swr_ctx);
+    ffi::avcodec_free_context(&mut (codec_ctx as *mut _));
+    ffi::avformat_close_input(&mut fmt_ctx);
+
+    decode_result.map(|_| pcm)
+}
+
+unsafe fn resample_frame_into(
+    swr_ctx: *mut ffi::SwrContext,
+    frame: *mut ffi::AVFrame,
+    channels: usize,
+    pcm: &mut Vec<f32>,
+) -> Result<(), AudioDecodeError> {
+    // Upper bound on output samples for this input frame, per swresample's docs.
+    let max_out_samples = ffi::swr_get_out_samples(swr_ctx, (*frame).nb_samples);
+    if max_out_samples < 0 {
+        return Err(AudioDecodeError::Decode("swr_get_out_samples failed".to_string()));
+    }
+
+    let mut out_buf = vec![0f32; max_out_samples as usize * channels];
+    let mut out_ptr = out_buf.as_mut_ptr() as *mut u8;
+    let converted = ffi::swr_convert(
+        swr_ctx,
+        &mut out_ptr,
+        max_out_samples,
+        (*frame).data.as_ptr() as *mut *const u8,
+        (*frame).nb_samples,
+    );
+    if converted < 0 {
+        return Err(AudioDecodeError::Decode("swr_convert failed".to_string()));
+    }
+
+    out_buf.truncate(converted as usize * channels);
+    pcm.extend_from_slice(&out_buf);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_decode_error_display() {
+        assert_eq!(
+            AudioDecodeError::UnsupportedFormat("no audio stream found".to_string()).to_string(),
+            "unsupported audio format: no audio stream found"
+        );
+        assert_eq!(
+            AudioDecodeError::Io("av_read_frame failed".to_string()).to_string(),
+            "audio I/O error: av_read_frame failed"
+        );
+        assert_eq!(
+            AudioDecodeError::Decode("swr_convert failed".to_string()).to_string(),
+            "audio decode error: swr_convert failed"
+        );
+    }
+}
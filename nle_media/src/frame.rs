@@ -1,5 +1,9 @@
 use anyhow::Result;
-use std::ffi::c_void;
+use ffmpeg_sys_next as ffi;
+use std::collections::HashMap;
+use std::ffi::{c_void, CString};
+use std::io::{Read, Seek, SeekFrom};
+use std::ptr;
 
 #[derive(Debug)]
 pub enum FrameData {
@@ -53,6 +57,547 @@ impl MediaSource for MockSource {
     }
 }
 
+// FFmpeg returns this for "decoder needs more input before it can produce a frame";
+// it is not a real error and callers must keep feeding packets.
+const AVERROR_EAGAIN: i32 = -(ffi::EAGAIN as i32);
+
+const AVIO_BUFFER_SIZE: usize = 4096;
+
+struct AvioReaderHandle {
+    inner: Box<dyn ReadSeek>,
+}
+
+trait ReadSeek: Read + Seek + Send {}
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+unsafe extern "C" fn avio_read_packet(opaque: *mut c_void, buf: *mut u8, buf_size: i32) -> i32 {
+    // Recover the boxed handle without dropping it: `AvioSource` owns this
+    // pointer for the AVIOContext's whole lifetime, the callback only borrows it.
+    let mut handle = Box::from_raw(opaque as *mut AvioReaderHandle);
+    let slice = std::slice::from_raw_parts_mut(buf, buf_size.max(0) as usize);
+    let ret = match handle.inner.read(slice) {
+        Ok(0) => ffi::AVERROR_EOF,
+        Ok(n) => n as i32,
+        Err(_) => ffi::AVERROR_EOF,
+    };
+    std::mem::forget(handle);
+    ret
+}
+
+unsafe extern "C" fn avio_seek(opaque: *mut c_void, offset: i64, whence: i32) -> i64 {
+    let mut handle = Box::from_raw(opaque as *mut AvioReaderHandle);
+
+    let ret = if whence & ffi::AVSEEK_SIZE != 0 {
+        match handle.inner.seek(SeekFrom::End(0)) {
+            Ok(len) => len as i64,
+            Err(_) => -1,
+        }
+    } else {
+        let from = match whence & !ffi::AVSEEK_FORCE {
+            0 => SeekFrom::Start(offset as u64), // libc::SEEK_SET
+            1 => SeekFrom::Current(offset),      // libc::SEEK_CUR
+            2 => SeekFrom::End(offset),          // libc::SEEK_END
+            _ => {
+                std::mem::forget(handle);
+                return -1;
+            }
+        };
+        match handle.inner.seek(from) {
+            Ok(pos) => pos as i64,
+            Err(_) => -1,
+        }
+    };
+
+    std::mem::forget(handle);
+    ret
+}
+
+/// Owns the `AVIOContext`, its read buffer, and the boxed Rust `Read + Seek` handle
+/// the `extern "C"` trampolines recover on each callback.
+pub(crate) struct AvioSource {
+    avio_ctx: *mut ffi::AVIOContext,
+    opaque: *mut c_void,
+}
+
+impl AvioSource {
+    pub(crate) fn new<R: Read + Seek + Send + 'static>(reader: R) -> Result<Self> {
+        let handle = Box::new(AvioReaderHandle {
+            inner: Box::new(reader),
+        });
+        let opaque = Box::into_raw(handle) as *mut c_void;
+
+        unsafe {
+            let buffer = ffi::av_malloc(AVIO_BUFFER_SIZE) as *mut u8;
+            if buffer.is_null() {
+                drop(Box::from_raw(opaque as *mut AvioReaderHandle));
+                return Err(anyhow::anyhow!("av_malloc failed for AVIO buffer"));
+            }
+
+            let avio_ctx = ffi::avio_alloc_context(
+                buffer,
+                AVIO_BUFFER_SIZE as i32,
+                0, // read-only: no write_packet callback
+                opaque,
+                Some(avio_read_packet),
+                None,
+                Some(avio_seek),
+            );
+            if avio_ctx.is_null() {
+                ffi::av_free(buffer as *mut c_void);
+                drop(Box::from_raw(opaque as *mut AvioReaderHandle));
+                return Err(anyhow::anyhow!("avio_alloc_context failed"));
+            }
+
+            Ok(Self { avio_ctx, opaque })
+        }
+    }
+}
+
+impl Drop for AvioSource {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.avio_ctx.is_null() {
+                ffi::av_freep(&mut (*self.avio_ctx).buffer as *mut _ as *mut c_void);
+                ffi::avio_context_free(&mut self.avio_ctx);
+            }
+            drop(Box::from_raw(self.opaque as *mut AvioReaderHandle));
+        }
+    }
+}
+
+/// Builds an `AVFormatContext` reading through a custom `AVIOContext` over any
+/// `Read + Seek` handle, and opens it (`avformat_open_input`). Shared by
+/// `FfmpegSource::from_reader` and `audio_decoder::decode_to_pcm`, which both need
+/// to decode from an in-memory buffer but otherwise walk the format differently
+/// (best video stream vs. best audio stream).
+pub(crate) unsafe fn open_custom_io_format_context<R: Read + Seek + Send + 'static>(
+    reader: R,
+) -> Result<(*mut ffi::AVFormatContext, AvioSource)> {
+    let avio = AvioSource::new(reader)?;
+
+    let mut fmt_ctx = ffi::avformat_alloc_context();
+    if fmt_ctx.is_null() {
+        return Err(anyhow::anyhow!("avformat_alloc_context failed"));
+    }
+    (*fmt_ctx).pb = avio.avio_ctx;
+    (*fmt_ctx).flags |= ffi::AVFMT_FLAG_CUSTOM_IO as i32;
+
+    if ffi::avformat_open_input(&mut fmt_ctx, ptr::null(), ptr::null_mut(), ptr::null_mut()) < 0 {
+        ffi::avformat_free_context(fmt_ctx);
+        return Err(anyhow::anyhow!("avformat_open_input failed for custom AVIO source"));
+    }
+
+    Ok((fmt_ctx, avio))
+}
+
+/// Decodes a file (or, via [`FfmpegSource::from_reader`], any `Read + Seek` handle) through
+/// libavformat/libavcodec. One `AVCodecContext` is kept per decoded stream, mirroring
+/// how a typical player structures its decode state.
+pub struct FfmpegSource {
+    fmt_ctx: *mut ffi::AVFormatContext,
+    decoders: HashMap<i32, *mut ffi::AVCodecContext>,
+    hw_device_ctx: *mut ffi::AVBufferRef,
+    video_stream_index: i32,
+    // Kept alive for as long as the format context reads through it; dropped
+    // after `fmt_ctx` is closed (field drop order follows declaration order).
+    avio: Option<AvioSource>,
+}
+
+impl FfmpegSource {
+    pub fn open(path: &str) -> Result<Self> {
+        unsafe {
+            let mut fmt_ctx: *mut ffi::AVFormatContext = ptr::null_mut();
+            let c_path = CString::new(path)?;
+            if ffi::avformat_open_input(
+                &mut fmt_ctx,
+                c_path.as_ptr(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            ) < 0
+            {
+                return Err(anyhow::anyhow!("avformat_open_input failed for {}", path));
+            }
+
+            Self::from_format_context(fmt_ctx, None)
+        }
+    }
+
+    /// Opens a source backed by any `Read + Seek` handle (in-memory buffers, mmap'd
+    /// assets, HTTP byte-range streams) instead of a path libavformat can open itself.
+    pub fn from_reader<R: Read + Seek + Send + 'static>(reader: R) -> Result<Self> {
+        unsafe {
+            let (fmt_ctx, avio) = open_custom_io_format_context(reader)?;
+            Self::from_format_context(fmt_ctx, Some(avio))
+        }
+    }
+
+    /// Shared setup once an `AVFormatContext` has been opened, whether from a path
+    /// (`open`) or from a custom `AVIOContext` (`from_reader`).
+    unsafe fn from_format_context(
+        mut fmt_ctx: *mut ffi::AVFormatContext,
+        avio: Option<AvioSource>,
+    ) -> Result<Self> {
+        if ffi::avformat_find_stream_info(fmt_ctx, ptr::null_mut()) < 0 {
+            ffi::avformat_close_input(&mut fmt_ctx);
+            return Err(anyhow::anyhow!("avformat_find_stream_info failed"));
+        }
+
+        let video_stream_index = ffi::av_find_best_stream(
+            fmt_ctx,
+            ffi::AVMediaType::AVMEDIA_TYPE_VIDEO,
+            -1,
+            -1,
+            ptr::null_mut(),
+            0,
+        );
+        if video_stream_index < 0 {
+            ffi::avformat_close_input(&mut fmt_ctx);
+            return Err(anyhow::anyhow!("no video stream found"));
+        }
+
+        let (codec_ctx, hw_device_ctx) = match Self::open_decoder(fmt_ctx, video_stream_index) {
+            Ok(ctx) => ctx,
+            Err(e) => {
+                ffi::avformat_close_input(&mut fmt_ctx);
+                return Err(e);
+            }
+        };
+
+        let mut decoders = HashMap::new();
+        decoders.insert(video_stream_index, codec_ctx);
+
+        Ok(Self {
+            fmt_ctx,
+            decoders,
+            hw_device_ctx,
+            video_stream_index,
+            avio,
+        })
+    }
+
+    unsafe fn open_decoder(
+        fmt_ctx: *mut ffi::AVFormatContext,
+        stream_index: i32,
+    ) -> Result<(*mut ffi::AVCodecContext, *mut ffi::AVBufferRef)> {
+        let stream = *(*fmt_ctx).streams.offset(stream_index as isize);
+        let codec_par = (*stream).codecpar;
+        let decoder = ffi::avcodec_find_decoder((*codec_par).codec_id);
+        if decoder.is_null() {
+            return Err(anyhow::anyhow!("unsupported codec id {:?}", (*codec_par).codec_id));
+        }
+
+        let codec_ctx = ffi::avcodec_alloc_context3(decoder);
+        if codec_ctx.is_null() {
+            return Err(anyhow::anyhow!("avcodec_alloc_context3 failed"));
+        }
+        if ffi::avcodec_parameters_to_context(codec_ctx, codec_par) < 0 {
+            ffi::avcodec_free_context(&mut (codec_ctx as *mut _));
+            return Err(anyhow::anyhow!("avcodec_parameters_to_context failed"));
+        }
+
+        // `hw_device_ctx` and `get_format` must be installed before `avcodec_open2`:
+        // per `AVCodecContext.hw_device_ctx`'s documented contract, the decoder reads
+        // it during open to decide which hwaccel to negotiate, and `get_format` is
+        // the callback libavcodec calls to let us pick the hw pixel format out of
+        // its candidate list (see ffmpeg's doc/examples/hw_decode.c). Installing
+        // either only after `avcodec_open2` has no effect on a real decoder.
+        let hw_device_ctx = Self::init_hwaccel(codec_ctx);
+
+        if ffi::avcodec_open2(codec_ctx, decoder, ptr::null_mut()) < 0 {
+            let mut ctx = codec_ctx;
+            ffi::avcodec_free_context(&mut ctx);
+            if !hw_device_ctx.is_null() {
+                ffi::av_buffer_unref(&mut (hw_device_ctx as *mut _));
+            }
+            return Err(anyhow::anyhow!("avcodec_open2 failed"));
+        }
+
+        Ok((codec_ctx, hw_device_ctx))
+    }
+
+    /// Best-effort hardware device setup (VAAPI on Linux, D3D11VA on Windows,
+    /// VideoToolbox on macOS). Absence of a working accelerator is not fatal: the
+    /// decoder then runs in software and its frames need no `av_hwframe_transfer_data`. Must run
+    /// before `avcodec_open2` (see `open_decoder`).
+    unsafe fn init_hwaccel(codec_ctx: *mut ffi::AVCodecContext) -> *mut ffi::AVBufferRef {
+        let (hw_type, hw_pix_fmt) = if cfg!(target_os = "linux") {
+            (ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI, ffi::AVPixelFormat::AV_PIX_FMT_VAAPI)
+        } else if cfg!(target_os = "windows") {
+            // D3D11VA rather than DXVA2: its decoded-frame format is AV_PIX_FMT_D3D11,
+            // which `transfer_frame` reads back to the CPU like any other hw frame.
+            (ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA, ffi::AVPixelFormat::AV_PIX_FMT_D3D11)
+        } else if cfg!(target_os = "macos") {
+            (
+                ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VIDEOTOOLBOX,
+                ffi::AVPixelFormat::AV_PIX_FMT_VIDEOTOOLBOX,
+            )
+        } else {
+            return ptr::null_mut();
+        };
+
+        let mut hw_device_ctx: *mut ffi::AVBufferRef = ptr::null_mut();
+        let ret =
+            ffi::av_hwdevice_ctx_create(&mut hw_device_ctx, hw_type, ptr::null(), ptr::null_mut(), 0);
+        if ret < 0 {
+            return ptr::null_mut();
+        }
+
+        (*codec_ctx).hw_device_ctx = ffi::av_buffer_ref(hw_device_ctx);
+        // `get_format` has no user-data parameter, so stash the pixel format we
+        // want in `opaque` (unused by libavcodec itself) for the callback to read.
+        (*codec_ctx).opaque = (hw_pix_fmt as i32) as isize as *mut c_void;
+        (*codec_ctx).get_format = Some(Self::negotiate_hw_pix_fmt);
+        hw_device_ctx
+    }
+
+    /// `AVCodecContext.get_format` callback: picks the hw pixel format stashed in
+    /// `opaque` out of the decoder's candidate list, falling back to the decoder's
+    /// own first choice if our hwaccel's format isn't offered (e.g. the codec
+    /// turned out not to support hw decode after all).
+    unsafe extern "C" fn negotiate_hw_pix_fmt(
+        ctx: *mut ffi::AVCodecContext,
+        pix_fmts: *const ffi::AVPixelFormat,
+    ) -> ffi::AVPixelFormat {
+        let target = (*ctx).opaque as isize as i32;
+        let mut candidate = pix_fmts;
+        while *candidate as i32 != ffi::AVPixelFormat::AV_PIX_FMT_NONE as i32 {
+            if *candidate as i32 == target {
+                return *candidate;
+            }
+            candidate = candidate.add(1);
+        }
+        *pix_fmts
+    }
+
+    fn decoder_for(&mut self, stream_index: i32) -> Option<*mut ffi::AVCodecContext> {
+        self.decoders.get(&stream_index).copied()
+    }
+
+    fn stream_time_base(&self, stream_index: i32) -> ffi::AVRational {
+        unsafe {
+            let stream = *(*self.fmt_ctx).streams.offset(stream_index as isize);
+            (*stream).time_base
+        }
+    }
+
+    /// Converts our microsecond timecode into the stream's native `time_base` units,
+    /// which is what `av_seek_frame`/PTS comparisons need.
+    fn us_to_stream_ts(time: u64, time_base: ffi::AVRational) -> i64 {
+        ffi::av_rescale_q(
+            time as i64,
+            ffi::AVRational { num: 1, den: 1_000_000 },
+            time_base,
+        )
+    }
+
+    unsafe fn transfer_frame(frame: *mut ffi::AVFrame, width: u32, height: u32) -> Result<VideoFrame> {
+        let timecode = (*frame).pts.max(0) as u64;
+
+        if !(*frame).hw_frames_ctx.is_null() {
+            // True zero-copy export still needs real platform work we don't have
+            // yet: VAAPI's `frame->data[3]` is a `VASurfaceID` handle, not a
+            // dma-buf fd (that requires `av_hwframe_map`/`vaExportSurfaceHandle` to
+            // a `AV_PIX_FMT_DRM_PRIME` frame), and D3D11's `ID3D11Texture2D` needs
+            // `IDXGIResource1::CreateSharedHandle` to hand over an NT handle
+            // `FrameData::Dx12Handle` could actually use. Until those land, read the
+            // decoded surface back to the CPU rather than mislabeling the raw
+            // handle as something it isn't.
+            if (*frame).format == ffi::AVPixelFormat::AV_PIX_FMT_VIDEOTOOLBOX as i32 {
+                return Ok(VideoFrame {
+                    ptr: FrameData::MetalRef((*frame).data[3] as *mut c_void),
+                    timecode,
+                    width,
+                    height,
+                });
+            }
+        }
+
+        Self::transfer_software_frame(frame, width, height, timecode)
+    }
+
+    unsafe fn transfer_software_frame(
+        frame: *mut ffi::AVFrame,
+        width: u32,
+        height: u32,
+        timecode: u64,
+    ) -> Result<VideoFrame> {
+        let sw_frame = if !(*frame).hw_frames_ctx.is_null() {
+            let transferred = ffi::av_frame_alloc();
+            if ffi::av_hwframe_transfer_data(transferred, frame, 0) < 0 {
+                ffi::av_frame_free(&mut (transferred as *mut _));
+                return Err(anyhow::anyhow!("av_hwframe_transfer_data failed"));
+            }
+            transferred
+        } else {
+            frame
+        };
+
+        let pix_fmt = (*sw_frame).format;
+        let result = if pix_fmt == ffi::AVPixelFormat::AV_PIX_FMT_RGBA as i32 {
+            Ok(Self::copy_rgba_plane(sw_frame, width, height))
+        } else {
+            Self::convert_to_rgba(sw_frame, pix_fmt, width, height)
+        };
+
+        if sw_frame != frame {
+            ffi::av_frame_free(&mut (sw_frame as *mut _));
+        }
+
+        Ok(VideoFrame {
+            ptr: FrameData::Cpu(result?),
+            timecode,
+            width,
+            height,
+        })
+    }
+
+    unsafe fn copy_rgba_plane(frame: *mut ffi::AVFrame, width: u32, height: u32) -> Vec<u8> {
+        let stride = (*frame).linesize[0] as usize;
+        let row_bytes = width as usize * 4;
+        let mut data = Vec::with_capacity(row_bytes * height as usize);
+        for row in 0..height as usize {
+            let row_ptr = (*frame).data[0].add(row * stride);
+            data.extend_from_slice(std::slice::from_raw_parts(row_ptr, row_bytes));
+        }
+        data
+    }
+
+    /// Converts a decoded frame in its native pixel format (YUV420P, NV12, etc. —
+    /// i.e. virtually every software-decoded frame) into packed RGBA8 via
+    /// `sws_scale`, mirroring the RGBA-to-encoder-format conversion `nle_export`
+    /// does in the opposite direction.
+    unsafe fn convert_to_rgba(
+        frame: *mut ffi::AVFrame,
+        pix_fmt: i32,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>> {
+        let sws_ctx = ffi::sws_getContext(
+            width as i32,
+            height as i32,
+            std::mem::transmute(pix_fmt),
+            width as i32,
+            height as i32,
+            ffi::AVPixelFormat::AV_PIX_FMT_RGBA,
+            ffi::SWS_BILINEAR,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null(),
+        );
+        if sws_ctx.is_null() {
+            return Err(anyhow::anyhow!(
+                "sws_getContext failed for pixel format {}",
+                pix_fmt
+            ));
+        }
+
+        let dst_stride = [4 * width as i32, 0, 0, 0];
+        let row_bytes = width as usize * 4;
+        let mut data = vec![0u8; row_bytes * height as usize];
+        let mut dst_slices = [data.as_mut_ptr(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut()];
+
+        ffi::sws_scale(
+            sws_ctx,
+            (*frame).data.as_ptr() as *const *const u8,
+            (*frame).linesize.as_ptr(),
+            0,
+            height as i32,
+            dst_slices.as_mut_ptr(),
+            dst_stride.as_ptr(),
+        );
+        ffi::sws_freeContext(sws_ctx);
+
+        Ok(data)
+    }
+}
+
+unsafe impl Send for FfmpegSource {}
+unsafe impl Sync for FfmpegSource {}
+
+impl MediaSource for FfmpegSource {
+    fn get_frame_at(&mut self, time: u64) -> Result<VideoFrame> {
+        let stream_index = self.video_stream_index;
+        let codec_ctx = self
+            .decoder_for(stream_index)
+            .ok_or_else(|| anyhow::anyhow!("no decoder open for stream {}", stream_index))?;
+        let time_base = self.stream_time_base(stream_index);
+        let target_ts = Self::us_to_stream_ts(time, time_base);
+
+        unsafe {
+            if ffi::av_seek_frame(
+                self.fmt_ctx,
+                stream_index,
+                target_ts,
+                ffi::AVSEEK_FLAG_BACKWARD,
+            ) < 0
+            {
+                return Err(anyhow::anyhow!("av_seek_frame failed"));
+            }
+            ffi::avcodec_flush_buffers(codec_ctx);
+
+            let width = (*codec_ctx).width as u32;
+            let height = (*codec_ctx).height as u32;
+
+            let packet = ffi::av_packet_alloc();
+            let frame = ffi::av_frame_alloc();
+            let result = loop {
+                let read_ret = ffi::av_read_frame(self.fmt_ctx, packet);
+                if read_ret == ffi::AVERROR_EOF {
+                    ffi::avcodec_send_packet(codec_ctx, ptr::null());
+                } else if read_ret < 0 {
+                    break Err(anyhow::anyhow!("av_read_frame failed"));
+                } else if (*packet).stream_index != stream_index {
+                    ffi::av_packet_unref(packet);
+                    continue;
+                } else {
+                    let send_ret = ffi::avcodec_send_packet(codec_ctx, packet);
+                    ffi::av_packet_unref(packet);
+                    if send_ret < 0 && send_ret != AVERROR_EAGAIN {
+                        break Err(anyhow::anyhow!("avcodec_send_packet failed"));
+                    }
+                }
+
+                let recv_ret = ffi::avcodec_receive_frame(codec_ctx, frame);
+                if recv_ret == AVERROR_EAGAIN {
+                    if read_ret == ffi::AVERROR_EOF {
+                        break Err(anyhow::anyhow!("reached EOF before requested timecode"));
+                    }
+                    continue;
+                } else if recv_ret == ffi::AVERROR_EOF {
+                    break Err(anyhow::anyhow!("reached EOF before requested timecode"));
+                } else if recv_ret < 0 {
+                    break Err(anyhow::anyhow!("avcodec_receive_frame failed"));
+                }
+
+                // Discard frames decoded ahead of the seek point until we reach the
+                // requested timecode, same as any keyframe-seek decode loop.
+                if (*frame).pts >= target_ts {
+                    break Self::transfer_frame(frame, width, height);
+                }
+            };
+
+            ffi::av_frame_free(&mut (frame as *mut _));
+            ffi::av_packet_free(&mut (packet as *mut _));
+            result
+        }
+    }
+}
+
+impl Drop for FfmpegSource {
+    fn drop(&mut self) {
+        unsafe {
+            for (_, codec_ctx) in self.decoders.drain() {
+                let mut ctx = codec_ctx;
+                ffi::avcodec_free_context(&mut ctx);
+            }
+            if !self.hw_device_ctx.is_null() {
+                ffi::av_buffer_unref(&mut self.hw_device_ctx);
+            }
+            ffi::avformat_close_input(&mut self.fmt_ctx);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,337 @@
+use crate::RenderNode;
+use anyhow::Result;
+use nle_data::model::{Clip, Timeline};
+use nle_media::decoder::DecoderMessage;
+use nle_media::frame::{FrameData, VideoFrame};
+use std::collections::HashMap;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+use wgpu::util::DeviceExt;
+
+const BLEND_SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Fullscreen triangle, no vertex buffer needed.
+    var out: VertexOutput;
+    let x = f32(i32((vertex_index << 1u) & 2u)) * 2.0 - 1.0;
+    let y = f32(i32(vertex_index & 2u)) * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>((x + 1.0) * 0.5, (1.0 - y) * 0.5);
+    return out;
+}
+
+@group(0) @binding(0) var clip_texture: texture_2d<f32>;
+@group(0) @binding(1) var clip_sampler: sampler;
+
+struct Alpha {
+    value: f32,
+};
+@group(0) @binding(2) var<uniform> alpha: Alpha;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(clip_texture, clip_sampler, in.uv);
+    return vec4<f32>(sample.rgb, sample.a * alpha.value);
+}
+"#;
+
+/// One clip's decoded frame, already uploaded to a GPU texture and ready to be
+/// blended by [`TimelineCompositor::encode`].
+struct CompositedLayer {
+    bind_group: wgpu::BindGroup,
+}
+
+/// A [`RenderNode`] that walks a [`Timeline`] at a given timecode, pulls the visible
+/// clip from each video track through its decoder actor, uploads the resulting
+/// frame to a GPU texture, and blends the tracks top-down.
+///
+/// The decoder round-trip is async (it talks to [`DecoderActor`](nle_media::decoder::DecoderActor)
+/// over a channel), so frame fetch/upload happens in [`prepare`](Self::prepare); the
+/// `RenderNode::encode` call itself stays synchronous and only draws what `prepare`
+/// already staged, matching how `RenderEngine` drives nodes per frame.
+pub struct TimelineCompositor {
+    timeline: Timeline,
+    decoders: HashMap<Uuid, mpsc::Sender<DecoderMessage>>,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    layers: Vec<CompositedLayer>,
+}
+
+impl TimelineCompositor {
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat, timeline: Timeline) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("TimelineCompositor Blend Shader"),
+            source: wgpu::ShaderSource::Wgsl(BLEND_SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("TimelineCompositor Layer Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("TimelineCompositor Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("TimelineCompositor Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TimelineCompositor Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            timeline,
+            decoders: HashMap::new(),
+            pipeline,
+            bind_group_layout,
+            sampler,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn register_decoder(&mut self, asset_id: Uuid, sender: mpsc::Sender<DecoderMessage>) {
+        self.decoders.insert(asset_id, sender);
+    }
+
+    /// Maps a timeline timecode to the clip's source timecode, honoring the in-point
+    /// (`source_range.start`) baked into the clip.
+    fn source_time_for(clip: &Clip, timeline_time: u64) -> u64 {
+        let elapsed = timeline_time.saturating_sub(clip.timeline_range.start);
+        clip.source_range.start + elapsed
+    }
+
+    async fn fetch_frame(&self, clip: &Clip, timeline_time: u64) -> Result<VideoFrame> {
+        let sender = self
+            .decoders
+            .get(&clip.asset_uuid())
+            .ok_or_else(|| anyhow::anyhow!("no decoder registered for asset {}", clip.asset_uuid()))?;
+
+        let (response, rx) = oneshot::channel();
+        sender
+            .send(DecoderMessage::GetFrame {
+                time: Self::source_time_for(clip, timeline_time),
+                response,
+            })
+            .await
+            .map_err(|_| anyhow::anyhow!("decoder actor for asset {} is gone", clip.asset_uuid()))?;
+
+        rx.await
+            .map_err(|_| anyhow::anyhow!("decoder actor for asset {} dropped its reply", clip.asset_uuid()))?
+    }
+
+    fn upload_frame(&self, device: &wgpu::Device, queue: &wgpu::Queue, frame: VideoFrame) -> Result<wgpu::TextureView> {
+        let size = wgpu::Extent3d {
+            width: frame.width,
+            height: frame.height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = match frame.ptr {
+            FrameData::Cpu(data) => {
+                let texture = device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("TimelineCompositor Clip Texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                });
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    &data,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(4 * frame.width),
+                        rows_per_image: Some(frame.height),
+                    },
+                    size,
+                );
+                texture
+            }
+            external => self.import_external_texture(device, external, size)?,
+        };
+
+        Ok(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// Imports a decoder-owned GPU surface as a `wgpu::Texture` without a CPU
+    /// round-trip. `wgpu` has no cross-backend "import external memory" call, and
+    /// doing this for real means building a native `vk::Image` / `ID3D12Resource` /
+    /// `MTLTexture` from the raw dma-buf fd / shared handle / Metal ref ourselves
+    /// (`VkImportMemoryFdInfoKHR` + `vkCreateImage` on Vulkan, the D3D12 shared-handle
+    /// open on Windows) before handing it to `wgpu_hal`'s real `texture_from_raw`,
+    /// which only accepts an already-constructed native object, not a raw fd/handle.
+    /// That native-object construction is not implemented, so every external frame
+    /// kind is rejected with an error here, including the `FrameData::MetalRef`
+    /// that `FfmpegSource` returns for VideoToolbox-decoded frames.
+    fn import_external_texture(
+        &self,
+        _device: &wgpu::Device,
+        frame: FrameData,
+        _size: wgpu::Extent3d,
+    ) -> Result<wgpu::Texture> {
+        Err(anyhow::anyhow!(
+            "zero-copy GPU import for {:?} is not implemented yet; decode to FrameData::Cpu instead",
+            frame
+        ))
+    }
+
+    /// Fetches and uploads every visible clip for `time`, replacing the layer list
+    /// `encode` will draw on the next `RenderNode::encode` call. `video_tracks`
+    /// order is the z-order: index 0 is the bottom track.
+    pub async fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, time: u64) -> Result<()> {
+        let mut layers = Vec::new();
+
+        for track in &self.timeline.video_tracks {
+            let Some(clip) = track.query(time) else {
+                continue;
+            };
+
+            let frame = self.fetch_frame(clip, time).await?;
+            let view = self.upload_frame(device, queue, frame)?;
+
+            let alpha_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("TimelineCompositor Layer Alpha"),
+                contents: bytemuck::bytes_of(&clip.opacity),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("TimelineCompositor Layer Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: alpha_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            layers.push(CompositedLayer { bind_group });
+        }
+
+        self.layers = layers;
+        Ok(())
+    }
+}
+
+impl RenderNode for TimelineCompositor {
+    fn encode(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("TimelineCompositor Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        for layer in &self.layers {
+            // Bottom-to-top draw order with alpha blending gives top-down compositing.
+            pass.set_bind_group(0, &layer.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nle_data::model::TimeRange;
+
+    #[test]
+    fn test_source_time_for_honors_in_point() {
+        let clip = Clip::new(
+            "A".to_string(),
+            Uuid::new_v4(),
+            TimeRange::new(5_000_000, 10_000_000), // source in-point at 5s
+            TimeRange::new(2_000_000, 10_000_000), // placed at 2s on the timeline
+            0,
+        );
+
+        // At timeline time 3s the clip has played for 1s, so the source
+        // timecode should be the 5s in-point plus that 1s of elapsed time.
+        assert_eq!(TimelineCompositor::source_time_for(&clip, 3_000_000), 6_000_000);
+    }
+}
@@ -1,6 +1,9 @@
 use anyhow::Result;
 pub use wgpu;
 
+mod compositor;
+pub use compositor::TimelineCompositor;
+
 pub trait RenderNode {
     fn update(&mut self, _queue: &wgpu::Queue) {}
     fn encode(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView);